@@ -12,7 +12,9 @@ use crate::media_stream::track_remote::static_rtp::TrackRemoteStaticRTP;
 use crate::media_stream::track_remote::{TrackRemote, TrackRemoteEvent};
 use crate::peer_connection::PeerConnectionRef;
 use crate::peer_connection::transports::tcp_transport::RTCTcpTransport;
-use crate::peer_connection::transports::{SocketRecvResult, is_retryable_socket_recv_error};
+use crate::peer_connection::transports::{
+    IpFilter, SocketRecvResult, is_retryable_socket_recv_error,
+};
 use crate::rtp_transceiver::rtp_receiver::RtpReceiverImpl;
 use crate::rtp_transceiver::{RtpReceiver, RtpTransceiverImpl};
 use crate::runtime::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, Receiver, channel};
@@ -37,6 +39,7 @@ use rtc::shared::{FourTuple, TaggedBytesMut, TransportContext, TransportProtocol
 use rtc::{rtcp, rtp};
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
@@ -65,6 +68,8 @@ pub(crate) enum PeerConnectionDriverEvent {
     IncomingTcpStream(FourTuple, Arc<dyn AsyncTcpStream>),
     WriteNotify,
     IceGathering,
+    StunServerResolved(String, io::Result<Vec<SocketAddr>>),
+    TurnServerResolved(String, io::Result<Vec<SocketAddr>>),
     Close,
 }
 
@@ -81,6 +86,7 @@ where
     tcp_transport: RTCTcpTransport,
     mdns_socket: Option<Arc<dyn AsyncUdpSocket>>,
     udp_sockets: HashMap<SocketAddr, Arc<dyn AsyncUdpSocket>>,
+    ip_filter: Option<IpFilter>,
     ice_gathering_active: bool,
     stun_gathering_complete: bool,
     turn_gathering_complete: bool,
@@ -98,6 +104,7 @@ where
         mdns_socket: Option<Arc<dyn AsyncUdpSocket>>,
         udp_sockets: HashMap<SocketAddr, Arc<dyn AsyncUdpSocket>>,
         tcp_listeners: HashMap<SocketAddr, Arc<dyn AsyncTcpListener>>,
+        ip_filter: Option<IpFilter>,
     ) -> Result<Self> {
         if udp_sockets.is_empty() && tcp_listeners.is_empty() {
             return Err(Error::Other("no sockets or listeners available".to_owned()));
@@ -110,6 +117,7 @@ where
             mdns_socket,
             udp_sockets,
             tcp_transport: RTCTcpTransport::new(tcp_listeners),
+            ip_filter,
             ice_gathering_active: false,
             stun_gathering_complete: false,
             turn_gathering_complete: false,
@@ -351,7 +359,13 @@ where
             let n = msg.message.len();
             self.turn_relayer.handle_write(msg)?;
             Ok(n)
-        } else if let Some(udp_socket) = self.udp_sockets.get(&msg.transport.local_addr) {
+        } else if let Some(udp_socket) = self.udp_sockets.get(
+            // Host candidates under a 1:1 NAT mapping carry the external address.
+            &self
+                .stun_gatherer
+                .nat_local_addr(msg.transport.local_addr)
+                .unwrap_or(msg.transport.local_addr),
+        ) {
             Ok(udp_socket
                 .send_to(&msg.message, msg.transport.peer_addr)
                 .await?)
@@ -364,12 +378,19 @@ where
         }
     }
 
-    async fn handle_read(&mut self, msg: TaggedBytesMut) -> Result<()> {
+    async fn handle_read(&mut self, mut msg: TaggedBytesMut) -> Result<()> {
         if self.turn_relayer.is_turn_message(&msg) {
             self.turn_relayer.handle_read(msg)?;
         } else if self.stun_gatherer.is_stun_message(&msg) {
             self.stun_gatherer.handle_read(msg)?;
         } else {
+            if msg.transport.transport_protocol == TransportProtocol::UDP
+                && let Some(external_addr) = self
+                    .stun_gatherer
+                    .nat_external_addr(msg.transport.local_addr)
+            {
+                msg.transport.local_addr = external_addr;
+            }
             let mut core = self.inner.core.lock().await;
             core.handle_read(msg)?;
         }
@@ -777,7 +798,9 @@ where
                 };
 
                 if ice_gather_policy != RTCIceTransportPolicy::Relay {
-                    let candidates = self.tcp_transport.gather_candidates();
+                    let candidates = self
+                        .tcp_transport
+                        .gather_candidates(self.ip_filter.as_ref());
                     let mut core = self.inner.core.lock().await;
                     for candidate_init in candidates {
                         trace!("TCP LocalIceCandidate {:?}", candidate_init);
@@ -787,15 +810,39 @@ where
                     }
                 }
 
-                if self.stun_gatherer.state() != RTCIceGatheringState::Gathering
-                    && let Err(err) = self.stun_gatherer.gather().await
+                if self.stun_gatherer.state() != RTCIceGatheringState::Gathering {
+                    match self.stun_gatherer.gather() {
+                        Ok(servers) => self.resolve_servers(
+                            servers,
+                            PeerConnectionDriverEvent::StunServerResolved,
+                        ),
+                        Err(err) => error!("Failed to gather ice gathering: {}", err),
+                    }
+                }
+                if self.turn_relayer.state() != RTCIceGatheringState::Gathering {
+                    match self.turn_relayer.gather() {
+                        Ok(servers) => self.resolve_servers(
+                            servers,
+                            PeerConnectionDriverEvent::TurnServerResolved,
+                        ),
+                        Err(err) => error!("Failed to gather relay candidates: {}", err),
+                    }
+                }
+            }
+            PeerConnectionDriverEvent::StunServerResolved(server, resolved_addrs) => {
+                if let Err(err) = self
+                    .stun_gatherer
+                    .handle_event(RTCStunGatherEventIn::ServerResolved(server, resolved_addrs))
                 {
-                    error!("Failed to gather ice gathering: {}", err);
+                    error!("Failed to handle resolved STUN server: {}", err);
                 }
-                if self.turn_relayer.state() != RTCIceGatheringState::Gathering
-                    && let Err(err) = self.turn_relayer.gather().await
+            }
+            PeerConnectionDriverEvent::TurnServerResolved(server, resolved_addrs) => {
+                if let Err(err) = self
+                    .turn_relayer
+                    .handle_event(RTCTurnRelayEventIn::ServerResolved(server, resolved_addrs))
                 {
-                    error!("Failed to gather relay candidates: {}", err);
+                    error!("Failed to handle resolved TURN server: {}", err);
                 }
             }
             PeerConnectionDriverEvent::RemoteIceTcpPassiveCandidate(candidate) => {
@@ -820,6 +867,22 @@ where
         false
     }
 
+    /// Resolve STUN/TURN server addresses off the event loop, so host candidates
+    /// are not held back by DNS; each result comes back as a driver event.
+    fn resolve_servers(
+        &self,
+        servers: Vec<String>,
+        resolved: fn(String, io::Result<Vec<SocketAddr>>) -> PeerConnectionDriverEvent,
+    ) {
+        for server in servers {
+            let tx = self.inner.driver_event_tx.clone();
+            self.inner.runtime.spawn(Box::pin(async move {
+                let resolved_addrs = crate::runtime::resolve_host(&server).await;
+                let _ = tx.send(resolved(server, resolved_addrs)).await;
+            }));
+        }
+    }
+
     async fn populate_track_remote_codings(
         inner: Arc<PeerConnectionRef<I>>,
         receiver_id: RTCRtpReceiverId,
@@ -54,9 +54,9 @@ pub(crate) mod transports;
 
 use log::error;
 use std::collections::{HashMap, HashSet};
-use std::net::ToSocketAddrs;
+use std::net::{IpAddr, ToSocketAddrs};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::data_channel::{DataChannel, DataChannelEvent, DataChannelImpl};
 use crate::media_stream::{track_local::TrackLocal, track_remote::TrackRemote};
@@ -69,8 +69,7 @@ use driver::{
     DATA_CHANNEL_EVENT_CHANNEL_CAPACITY, PEER_CONNECTION_DRIVER_EVENT_CHANNEL_CAPACITY,
     PeerConnectionDriver,
 };
-use transports::stun_gatherer::RTCStunGatherer;
-use transports::turn_relayer::RTCTurnRelayer;
+use transports::{GatherOptions, NatOneToOne, new_gatherers};
 
 use rtc::data_channel::{RTCDataChannelId, RTCDataChannelInit};
 use rtc::ice::mdns::MulticastDnsMode;
@@ -176,6 +175,7 @@ where
     udp_addrs: Vec<A>,
     tcp_addrs: Vec<A>,
     dedicated_reactor: bool,
+    gather_options: GatherOptions,
 }
 
 impl<A: ToSocketAddrs> Default for PeerConnectionBuilder<A, NoopInterceptor> {
@@ -188,6 +188,7 @@ impl<A: ToSocketAddrs> Default for PeerConnectionBuilder<A, NoopInterceptor> {
             udp_addrs: vec![],
             tcp_addrs: vec![],
             dedicated_reactor: false,
+            gather_options: GatherOptions::default(),
         }
     }
 }
@@ -238,6 +239,7 @@ where
             udp_addrs: self.udp_addrs,
             tcp_addrs: self.tcp_addrs,
             dedicated_reactor: self.dedicated_reactor,
+            gather_options: self.gather_options,
         }
     }
 
@@ -265,6 +267,62 @@ where
         self
     }
 
+    /// Bounds how long server reflexive (STUN) gathering may take.
+    ///
+    /// Host candidates are emitted immediately and srflx candidates as each STUN
+    /// server answers; once the timeout elapses, STUN servers still resolving or
+    /// not yet answered are abandoned so end-of-candidates is not held back by an
+    /// unreachable server. Defaults to `None`, which relies on the STUN client's
+    /// own transaction timeouts.
+    pub fn with_srflx_gather_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.gather_options.timeouts.srflx = timeout;
+        self
+    }
+
+    /// Bounds how long relay (TURN) gathering may take.
+    ///
+    /// TURN servers still resolving and allocations still pending when the
+    /// timeout elapses are abandoned, while relay candidates already allocated
+    /// are kept. Defaults to `None`, which relies on the TURN client's own
+    /// transaction timeouts.
+    pub fn with_relay_gather_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.gather_options.timeouts.relay = timeout;
+        self
+    }
+
+    /// Restricts ICE gathering to local IP addresses accepted by `ip_filter`.
+    ///
+    /// Bound addresses the filter rejects produce no host, srflx or relay
+    /// candidates, so internal addresses (docker bridges, VPN tunnels) are not
+    /// advertised to the remote peer. By default every bound address is used.
+    pub fn with_ip_filter<F>(mut self, ip_filter: F) -> Self
+    where
+        F: Fn(IpAddr) -> bool + Send + Sync + 'static,
+    {
+        self.gather_options.ip_filter = Some(Arc::new(ip_filter));
+        self
+    }
+
+    /// Advertises external IPs of a 1:1 NAT in place of local IPs.
+    ///
+    /// Each mapping is `(external_ip, local_ip)`; a `None` local IP applies to
+    /// every local IP of the same address family without a mapping of its own.
+    /// With [`RTCIceCandidateType::Host`] host candidates carry the external IP,
+    /// with [`RTCIceCandidateType::Srflx`] a srflx candidate with the external IP
+    /// is added next to each host candidate. Either way mapped addresses skip
+    /// STUN servers. Any other candidate type makes [`build`](Self::build) fail.
+    pub fn with_nat_1to1_ips(
+        mut self,
+        mappings: Vec<(IpAddr, Option<IpAddr>)>,
+        candidate_type: RTCIceCandidateType,
+    ) -> Self {
+        self.gather_options.nat_1to1 = Some(NatOneToOne {
+            mappings,
+            candidate_type,
+        });
+        self
+    }
+
     /// Run this peer connection's driver on its own dedicated OS thread with a
     /// single-threaded reactor, instead of on the shared async runtime.
     ///
@@ -297,6 +355,18 @@ where
             default_runtime().ok_or_else(|| std::io::Error::other("no async runtime found"))?
         };
 
+        if let Some(nat_1to1) = &self.gather_options.nat_1to1
+            && !matches!(
+                nat_1to1.candidate_type,
+                RTCIceCandidateType::Host | RTCIceCandidateType::Srflx
+            )
+        {
+            return Err(Error::Other(format!(
+                "unsupported 1:1 NAT candidate type {}",
+                nat_1to1.candidate_type
+            )));
+        }
+
         let core = self.builder.build()?;
 
         PeerConnectionImpl::new(
@@ -308,6 +378,7 @@ where
             self.udp_addrs,
             self.tcp_addrs,
             self.dedicated_reactor,
+            self.gather_options,
         )
         .await
     }
@@ -523,6 +594,7 @@ where
         udp_addrs: Vec<A>,
         tcp_addrs: Vec<A>,
         dedicated_reactor: bool,
+        gather_options: GatherOptions,
     ) -> Result<Self> {
         // Bind the std sockets up front (synchronous, and needed to compute the
         // local addresses used for ICE gathering / SDP). Wrapping them into async
@@ -580,9 +652,9 @@ where
             .iter()
             .map(|(addr, _)| *addr)
             .collect::<Vec<_>>();
-        let stun_gatherer =
-            RTCStunGatherer::new(local_addrs.clone(), ice_servers.clone(), ice_gather_policy);
-        let turn_relayer = RTCTurnRelayer::new(local_addrs, ice_servers, ice_gather_policy);
+        let (stun_gatherer, turn_relayer) =
+            new_gatherers(local_addrs, ice_servers, ice_gather_policy, &gather_options);
+        let ip_filter = gather_options.ip_filter;
 
         // Init-result oneshot. `new()` awaits this so that socket wrapping and
         // driver construction errors propagate out of `build()`, instead of being
@@ -621,6 +693,7 @@ where
                     async_mdns_socket,
                     async_udp_sockets,
                     async_tcp_listeners,
+                    ip_filter,
                 )
                 .await
             }
@@ -1060,3 +1133,130 @@ where
         core.get_stats(now, selector)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::transports::stun_gatherer::RTCStunGatherEventOut;
+    use super::transports::turn_relayer::RTCTurnRelayEventIn;
+    use super::*;
+    use rtc::peer_connection::configuration::{RTCIceServer, RTCIceTransportPolicy};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn builder_gather_timeouts_reach_gatherers() {
+        let srflx_timeout = Duration::from_secs(2);
+        let relay_timeout = Duration::from_secs(3);
+        let builder = PeerConnectionBuilder::<String>::new()
+            .with_srflx_gather_timeout(Some(srflx_timeout))
+            .with_relay_gather_timeout(Some(relay_timeout));
+
+        let (mut stun_gatherer, mut turn_relayer) = new_gatherers(
+            vec!["127.0.0.1:50000".parse().expect("valid local address")],
+            vec![RTCIceServer {
+                urls: vec![
+                    "stun:127.0.0.1:3478".to_owned(),
+                    "turn:127.0.0.1:3478?transport=udp".to_owned(),
+                ],
+                username: "user".to_owned(),
+                credential: "pass".to_owned(),
+            }],
+            RTCIceTransportPolicy::All,
+            &builder.gather_options,
+        );
+
+        // Before any server resolves, the gather deadline is the only timer.
+        let before_gather = Instant::now();
+        stun_gatherer.gather().expect("STUN gather should start");
+        turn_relayer.gather().expect("TURN gather should start");
+        let after_gather = Instant::now();
+
+        let srflx_deadline = stun_gatherer.poll_timeout().expect("srflx deadline armed");
+        assert!(srflx_deadline >= before_gather + srflx_timeout);
+        assert!(srflx_deadline <= after_gather + srflx_timeout);
+        let relay_deadline = turn_relayer.poll_timeout().expect("relay deadline armed");
+        assert!(relay_deadline >= before_gather + relay_timeout);
+        assert!(relay_deadline <= after_gather + relay_timeout);
+    }
+
+    #[test]
+    fn builder_ip_filter_reaches_gatherers() {
+        let allowed_addr: SocketAddr = "127.0.0.1:50000".parse().expect("valid local address");
+        let rejected_addr: SocketAddr = "192.0.2.1:50000".parse().expect("valid local address");
+        let builder =
+            PeerConnectionBuilder::<String>::new().with_ip_filter(|ip: IpAddr| ip.is_loopback());
+
+        let (mut stun_gatherer, mut turn_relayer) = new_gatherers(
+            vec![allowed_addr, rejected_addr],
+            vec![RTCIceServer {
+                urls: vec!["turn:127.0.0.1:3478?transport=udp".to_owned()],
+                username: "user".to_owned(),
+                credential: "pass".to_owned(),
+            }],
+            RTCIceTransportPolicy::All,
+            &builder.gather_options,
+        );
+
+        stun_gatherer.gather().expect("STUN gather should start");
+        let mut host_candidates = 0;
+        while let Some(event) = stun_gatherer.poll_event() {
+            if matches!(event, RTCStunGatherEventOut::LocalIceCandidate(_)) {
+                host_candidates += 1;
+            }
+        }
+        assert_eq!(host_candidates, 1, "only the loopback address is gathered");
+
+        for server in turn_relayer.gather().expect("TURN gather should start") {
+            turn_relayer
+                .handle_event(RTCTurnRelayEventIn::ServerResolved(
+                    server,
+                    Ok(vec!["127.0.0.1:3478".parse().expect("valid TURN address")]),
+                ))
+                .expect("relayer should accept the resolved TURN server");
+        }
+        let request = turn_relayer
+            .poll_write()
+            .expect("Allocate request from the allowed address");
+        assert_eq!(request.transport.local_addr, allowed_addr);
+        assert!(turn_relayer.poll_write().is_none());
+    }
+
+    #[test]
+    fn builder_nat_1to1_ips_reach_gatherers() {
+        let local_addr: SocketAddr = "10.0.0.2:50000".parse().expect("valid local address");
+        let external_addr: SocketAddr =
+            "203.0.113.7:50000".parse().expect("valid external address");
+        let builder = PeerConnectionBuilder::<String>::new()
+            .with_nat_1to1_ips(vec![(external_addr.ip(), None)], RTCIceCandidateType::Host);
+
+        let (mut stun_gatherer, _) = new_gatherers(
+            vec![local_addr],
+            vec![RTCIceServer {
+                urls: vec!["stun:127.0.0.1:3478".to_owned()],
+                ..Default::default()
+            }],
+            RTCIceTransportPolicy::All,
+            &builder.gather_options,
+        );
+
+        let servers = stun_gatherer.gather().expect("STUN gather should start");
+        assert!(servers.is_empty(), "mapped addresses skip STUN servers");
+        match stun_gatherer.poll_event() {
+            Some(RTCStunGatherEventOut::LocalIceCandidate(candidate)) => {
+                assert!(candidate.candidate.contains(" 203.0.113.7 50000 typ host"));
+            }
+            event => panic!("expected a host candidate, got {:?}", event),
+        }
+        assert!(matches!(
+            stun_gatherer.poll_event(),
+            Some(RTCStunGatherEventOut::StunGatheringComplete)
+        ));
+        assert_eq!(
+            stun_gatherer.nat_external_addr(local_addr),
+            Some(external_addr)
+        );
+        assert_eq!(
+            stun_gatherer.nat_local_addr(external_addr),
+            Some(local_addr)
+        );
+    }
+}
@@ -1,11 +1,90 @@
+use rtc::peer_connection::configuration::{RTCIceServer, RTCIceTransportPolicy};
+use rtc::peer_connection::transport::RTCIceCandidateType;
 use rtc::shared::FourTuple;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) mod stun_gatherer;
 pub(crate) mod tcp_transport;
 pub(crate) mod turn_relayer;
 
+use stun_gatherer::RTCStunGatherer;
+use turn_relayer::RTCTurnRelayer;
+
+/// Per-candidate-type bounds on ICE gathering; `None` leaves a type unbounded.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct GatherTimeouts {
+    pub(crate) srflx: Option<Duration>,
+    pub(crate) relay: Option<Duration>,
+}
+
+/// Decides whether a local IP address may be used for ICE gathering.
+pub(crate) type IpFilter = Arc<dyn Fn(IpAddr) -> bool + Send + Sync>;
+
+/// 1:1 NAT mapping of local IPs to the external IPs advertised in their place.
+#[derive(Debug, Clone)]
+pub(crate) struct NatOneToOne {
+    /// `(external_ip, local_ip)` pairs; a `None` local IP maps every local IP
+    /// of the same address family that has no mapping of its own.
+    pub(crate) mappings: Vec<(IpAddr, Option<IpAddr>)>,
+    /// Either `Host` (replace host candidates) or `Srflx` (add srflx candidates).
+    pub(crate) candidate_type: RTCIceCandidateType,
+}
+
+impl NatOneToOne {
+    /// External IP advertised for `local_ip`, preferring an explicit mapping
+    pub(crate) fn external_ip(&self, local_ip: IpAddr) -> Option<IpAddr> {
+        self.mappings
+            .iter()
+            .find(|(_, mapped_local_ip)| *mapped_local_ip == Some(local_ip))
+            .or_else(|| {
+                self.mappings.iter().find(|(external_ip, mapped_local_ip)| {
+                    mapped_local_ip.is_none() && external_ip.is_ipv4() == local_ip.is_ipv4()
+                })
+            })
+            .map(|(external_ip, _)| *external_ip)
+    }
+}
+
+/// ICE gathering options configured on the peer connection builder.
+#[derive(Default, Clone)]
+pub(crate) struct GatherOptions {
+    pub(crate) timeouts: GatherTimeouts,
+    pub(crate) ip_filter: Option<IpFilter>,
+    pub(crate) nat_1to1: Option<NatOneToOne>,
+}
+
+pub(crate) fn is_gather_ip_allowed(ip_filter: Option<&IpFilter>, ip: IpAddr) -> bool {
+    ip_filter.is_none_or(|ip_filter| ip_filter(ip))
+}
+
+/// Create the srflx and relay gatherers for the given local UDP addresses.
+pub(crate) fn new_gatherers(
+    local_addrs: Vec<SocketAddr>,
+    ice_servers: Vec<RTCIceServer>,
+    ice_gather_policy: RTCIceTransportPolicy,
+    gather_options: &GatherOptions,
+) -> (RTCStunGatherer, RTCTurnRelayer) {
+    let stun_gatherer = RTCStunGatherer::new(
+        local_addrs.clone(),
+        ice_servers.clone(),
+        ice_gather_policy,
+        gather_options.timeouts.srflx,
+        gather_options.ip_filter.clone(),
+        gather_options.nat_1to1.clone(),
+    );
+    let turn_relayer = RTCTurnRelayer::new(
+        local_addrs,
+        ice_servers,
+        ice_gather_policy,
+        gather_options.timeouts.relay,
+        gather_options.ip_filter.clone(),
+    );
+    (stun_gatherer, turn_relayer)
+}
+
 pub(crate) enum SocketRecvResult {
     Packet {
         n: usize,
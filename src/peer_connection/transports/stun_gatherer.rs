@@ -4,11 +4,12 @@
 //! Unlike the old async version, this gatherer is a configuration object that holds
 //! the ICE servers and state.
 
-use crate::runtime;
+use super::{IpFilter, NatOneToOne, is_gather_ip_allowed};
 use rtc::ice::candidate::CandidateConfig;
 use rtc::peer_connection::configuration::{RTCIceServer, RTCIceTransportPolicy};
 use rtc::peer_connection::transport::{
     CandidateHostConfig, CandidateServerReflexiveConfig, RTCIceCandidate, RTCIceCandidateInit,
+    RTCIceCandidateType,
 };
 use rtc::sansio::Protocol;
 use rtc::shared::error::Error;
@@ -26,12 +27,15 @@ use rtc::stun::agent::StunEvent;
 use rtc::stun::message::Getter;
 use rtc::stun::xoraddr::XorMappedAddress;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub(crate) enum RTCStunGatherEventIn {
     SocketWriteFailure(FourTuple),
+    /// Result of resolving a STUN server address returned by `gather()`.
+    ServerResolved(String, io::Result<Vec<SocketAddr>>),
 }
 
 #[derive(Debug)]
@@ -49,7 +53,12 @@ pub(crate) struct RTCStunGatherer {
     ice_servers: Vec<RTCIceServer>,
     ice_gather_policy: RTCIceTransportPolicy,
     state: RTCIceGatheringState,
+    gather_timeout: Option<Duration>,
+    gather_deadline: Option<Instant>,
+    ip_filter: Option<IpFilter>,
+    nat_1to1: Option<NatOneToOne>,
 
+    pending_servers: HashSet<String>,
     stun_clients: HashMap<FourTuple, StunClient>,
 
     wouts: VecDeque<TaggedBytesMut>,
@@ -58,17 +67,31 @@ pub(crate) struct RTCStunGatherer {
 
 impl RTCStunGatherer {
     /// Create a new ICE gatherer with ICE servers and gather policy
+    ///
+    /// `gather_timeout` bounds how long srflx gathering waits for STUN servers
+    /// to resolve and answer; `None` relies on the STUN client's own
+    /// transaction timeouts. Local addresses rejected by `ip_filter` are not
+    /// used for host or srflx candidates. Local addresses mapped by `nat_1to1`
+    /// advertise their external IP instead of querying STUN servers.
     pub(crate) fn new(
         local_addrs: Vec<SocketAddr>,
         ice_servers: Vec<RTCIceServer>,
         ice_gather_policy: RTCIceTransportPolicy,
+        gather_timeout: Option<Duration>,
+        ip_filter: Option<IpFilter>,
+        nat_1to1: Option<NatOneToOne>,
     ) -> Self {
         Self {
             local_addrs,
             ice_servers,
             ice_gather_policy,
             state: RTCIceGatheringState::New,
+            gather_timeout,
+            gather_deadline: None,
+            ip_filter,
+            nat_1to1,
 
+            pending_servers: HashSet::new(),
             stun_clients: HashMap::new(),
 
             wouts: VecDeque::new(),
@@ -92,30 +115,90 @@ impl RTCStunGatherer {
         false
     }
 
-    pub(crate) async fn gather(&mut self) -> Result<(), Error> {
+    /// External address advertised in place of `local_addr` by host candidates
+    /// under a 1:1 NAT mapping
+    pub(crate) fn nat_external_addr(&self, local_addr: SocketAddr) -> Option<SocketAddr> {
+        let nat_1to1 = self.nat_1to1.as_ref()?;
+        if !matches!(nat_1to1.candidate_type, RTCIceCandidateType::Host) {
+            return None;
+        }
+        nat_1to1
+            .external_ip(local_addr.ip())
+            .map(|external_ip| SocketAddr::new(external_ip, local_addr.port()))
+    }
+
+    /// Local socket address behind an external address returned by
+    /// [`RTCStunGatherer::nat_external_addr`]
+    pub(crate) fn nat_local_addr(&self, external_addr: SocketAddr) -> Option<SocketAddr> {
+        self.gather_addrs()
+            .into_iter()
+            .find(|local_addr| self.nat_external_addr(*local_addr) == Some(external_addr))
+    }
+
+    /// Start gathering: host candidates are emitted right away, and the STUN
+    /// servers to resolve are returned so the caller can look them up off the
+    /// event loop and report back with [`RTCStunGatherEventIn::ServerResolved`].
+    pub(crate) fn gather(&mut self) -> Result<Vec<String>, Error> {
         self.state = RTCIceGatheringState::Gathering;
+        // Armed before resolving STUN servers so DNS lookups count against the timeout.
+        self.gather_deadline = self.gather_timeout.map(|timeout| Instant::now() + timeout);
         if self.ice_gather_policy != RTCIceTransportPolicy::Relay {
             self.gather_host_candidates()?;
-            self.gather_srflx_candidates().await?;
+            if !self.stun_addrs().is_empty() {
+                self.pending_servers = self.stun_server_addrs();
+            }
         }
-        if self.stun_clients.is_empty() && self.state != RTCIceGatheringState::Complete {
+        self.maybe_emit_gathering_complete();
+        Ok(self.pending_servers.iter().cloned().collect())
+    }
+
+    /// Report srflx gathering complete once no STUN server or client is outstanding
+    fn maybe_emit_gathering_complete(&mut self) {
+        if self.pending_servers.is_empty()
+            && self.stun_clients.is_empty()
+            && self.state == RTCIceGatheringState::Gathering
+        {
             self.state = RTCIceGatheringState::Complete;
+            self.gather_deadline = None;
             self.events
                 .push_back(RTCStunGatherEventOut::StunGatheringComplete);
         }
-        Ok(())
+    }
+
+    /// Local socket addresses allowed by the IP filter
+    fn gather_addrs(&self) -> Vec<SocketAddr> {
+        self.local_addrs
+            .iter()
+            .copied()
+            .filter(|local_addr| is_gather_ip_allowed(self.ip_filter.as_ref(), local_addr.ip()))
+            .collect()
+    }
+
+    /// Local socket addresses that need a STUN server to learn their srflx address
+    fn stun_addrs(&self) -> Vec<SocketAddr> {
+        self.gather_addrs()
+            .into_iter()
+            .filter(|local_addr| {
+                self.nat_1to1
+                    .as_ref()
+                    .is_none_or(|nat_1to1| nat_1to1.external_ip(local_addr.ip()).is_none())
+            })
+            .collect()
     }
 
     /// Gather host ICE candidates from a local socket address
     ///
     /// This is a pure function that creates host candidates without performing I/O.
+    /// Under a 1:1 NAT mapping, host candidates carry the external IP, or a srflx
+    /// candidate with the external IP is added next to each host candidate.
     fn gather_host_candidates(&mut self) -> Result<(), Error> {
-        for local_addr in &self.local_addrs {
+        for local_addr in self.gather_addrs() {
+            let host_addr = self.nat_external_addr(local_addr).unwrap_or(local_addr);
             let candidate = CandidateHostConfig {
                 base_config: CandidateConfig {
                     network: "udp".to_owned(),
-                    address: local_addr.ip().to_string(),
-                    port: local_addr.port(),
+                    address: host_addr.ip().to_string(),
+                    port: host_addr.port(),
                     component: 1,
                     ..Default::default()
                 },
@@ -127,15 +210,47 @@ impl RTCStunGatherer {
 
             self.events
                 .push_back(RTCStunGatherEventOut::LocalIceCandidate(candidate_init));
+
+            if let Some(nat_1to1) = &self.nat_1to1
+                && matches!(nat_1to1.candidate_type, RTCIceCandidateType::Srflx)
+                && let Some(external_ip) = nat_1to1.external_ip(local_addr.ip())
+            {
+                let candidate_init = RTCStunGatherer::new_srflx_candidate(
+                    SocketAddr::new(external_ip, local_addr.port()),
+                    local_addr,
+                )?;
+                self.events
+                    .push_back(RTCStunGatherEventOut::LocalIceCandidate(candidate_init));
+            }
         }
         Ok(())
     }
 
-    /// Gather server reflexive (srflx) ICE candidates via STUN
-    ///
-    /// This performs actual I/O to query STUN servers and should be called
-    /// in an async context.
-    async fn gather_srflx_candidates(&mut self) -> Result<(), Error> {
+    /// Create a srflx candidate for `srflx_addr` related to the local `base_addr`
+    fn new_srflx_candidate(
+        srflx_addr: SocketAddr,
+        base_addr: SocketAddr,
+    ) -> Result<RTCIceCandidateInit, Error> {
+        let candidate = CandidateServerReflexiveConfig {
+            base_config: CandidateConfig {
+                network: "udp".to_owned(),
+                address: srflx_addr.ip().to_string(),
+                port: srflx_addr.port(),
+                component: 1,
+                ..Default::default()
+            },
+            rel_addr: base_addr.ip().to_string(),
+            rel_port: base_addr.port(),
+            ..Default::default()
+        }
+        .new_candidate_server_reflexive()?;
+
+        RTCIceCandidate::from(&candidate).to_json()
+    }
+
+    /// STUN server addresses to resolve for srflx gathering
+    fn stun_server_addrs(&self) -> HashSet<String> {
+        let mut servers = HashSet::new();
         for ice_server in &self.ice_servers {
             for url in &ice_server.urls {
                 // Only handle stun: URLs for now
@@ -143,68 +258,59 @@ impl RTCStunGatherer {
                     continue;
                 }
 
-                for local_addr in &self.local_addrs {
-                    match RTCStunGatherer::gather_from_stun_server(*local_addr, url).await {
-                        Ok(stun_client) => {
-                            self.stun_clients.insert(
-                                FourTuple {
-                                    local_addr: stun_client.local_addr(),
-                                    peer_addr: stun_client.peer_addr(),
-                                },
-                                stun_client,
-                            );
-                        }
-                        Err(err) => {
-                            error!("Failed to gather stun client: {}", err);
-                        }
-                    }
-                }
+                // Add default port 3478 if not specified
+                let stun_server_addr_str = if url.contains(':') {
+                    url.strip_prefix("stun:").unwrap_or(url).to_string()
+                } else {
+                    format!("{}:3478", url.strip_prefix("stun:").unwrap_or(url))
+                };
+                servers.insert(stun_server_addr_str);
             }
         }
+        servers
+    }
 
-        Ok(())
+    /// Gather server reflexive (srflx) ICE candidates from a resolved STUN server
+    fn gather_srflx_candidates(&mut self, server: &str, resolved_addrs: &[SocketAddr]) {
+        debug!("Resolved STUN server {} to {:?}", server, resolved_addrs);
+
+        for local_addr in self.stun_addrs() {
+            // Filter addresses to match the local_addr IP version (IPv4 or IPv6)
+            let Some(stun_server_addr) = resolved_addrs
+                .iter()
+                .copied()
+                .find(|addr| addr.is_ipv4() == local_addr.is_ipv4())
+            else {
+                let ip_version = if local_addr.is_ipv4() { "IPv4" } else { "IPv6" };
+                error!(
+                    "Failed to resolve STUN server {} to {} address (local_addr is {})",
+                    server, ip_version, local_addr
+                );
+                continue;
+            };
+
+            match RTCStunGatherer::gather_from_stun_server(local_addr, stun_server_addr) {
+                Ok(stun_client) => {
+                    self.stun_clients.insert(
+                        FourTuple {
+                            local_addr: stun_client.local_addr(),
+                            peer_addr: stun_client.peer_addr(),
+                        },
+                        stun_client,
+                    );
+                }
+                Err(err) => {
+                    error!("Failed to gather stun client: {}", err);
+                }
+            }
+        }
     }
 
     /// Gather a single srflx candidate from a STUN server
-    async fn gather_from_stun_server(
+    fn gather_from_stun_server(
         local_addr: SocketAddr,
-        stun_url: &str,
+        stun_server_addr: SocketAddr,
     ) -> Result<StunClient, Error> {
-        // Resolve STUN server address (add default port 3478 if not specified)
-        let stun_server_addr_str = if stun_url.contains(':') {
-            stun_url
-                .strip_prefix("stun:")
-                .unwrap_or(stun_url)
-                .to_string()
-        } else {
-            format!(
-                "{}:3478",
-                stun_url.strip_prefix("stun:").unwrap_or(stun_url)
-            )
-        };
-
-        debug!("Resolving STUN server: {}", stun_server_addr_str);
-
-        // Resolve hostname to IP address using runtime-agnostic helper
-        let resolved_addrs = runtime::resolve_host(&stun_server_addr_str).await?;
-
-        // Filter addresses to match the local_addr IP version (IPv4 or IPv6)
-        let stun_server_addr: SocketAddr = resolved_addrs
-            .into_iter()
-            .find(|addr| addr.is_ipv4() == local_addr.is_ipv4())
-            .ok_or_else(|| {
-                let ip_version = if local_addr.is_ipv4() { "IPv4" } else { "IPv6" };
-                Error::Other(format!(
-                    "Failed to resolve STUN server hostname to {} address (local_addr is {})",
-                    ip_version, local_addr
-                ))
-            })?;
-
-        debug!(
-            "Resolved STUN server {} to {}",
-            stun_server_addr_str, stun_server_addr
-        );
-
         debug!("STUN client bound to {}", local_addr);
 
         // Create STUN client using the sans-I/O pattern
@@ -264,15 +370,20 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
             RTCStunGatherEventIn::SocketWriteFailure(four_tuple) => {
                 if let Some(mut stun_client) = self.stun_clients.remove(&four_tuple) {
                     let _ = stun_client.close();
-
-                    if self.stun_clients.is_empty() && self.state != RTCIceGatheringState::Complete
-                    {
-                        self.state = RTCIceGatheringState::Complete;
-                        self.events
-                            .push_back(RTCStunGatherEventOut::StunGatheringComplete);
-                    }
+                    self.maybe_emit_gathering_complete();
                 }
             }
+            RTCStunGatherEventIn::ServerResolved(server, resolved_addrs) => {
+                // Ignore lookups that finished after the gather deadline or close().
+                if !self.pending_servers.remove(&server) {
+                    return Ok(());
+                }
+                match resolved_addrs {
+                    Ok(resolved_addrs) => self.gather_srflx_candidates(&server, &resolved_addrs),
+                    Err(err) => error!("Failed to resolve STUN server {}: {}", server, err),
+                }
+                self.maybe_emit_gathering_complete();
+            }
         }
         Ok(())
     }
@@ -281,6 +392,13 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
         let mut four_tuples = HashSet::new();
         for stun_client in self.stun_clients.values_mut() {
             while let Some(event) = stun_client.poll_event() {
+                // The binding request is the only transaction of this client, so
+                // any outcome, including an unusable response, ends srflx
+                // gathering for its four-tuple.
+                four_tuples.insert(FourTuple {
+                    local_addr: stun_client.local_addr(),
+                    peer_addr: stun_client.peer_addr(),
+                });
                 match event {
                     StunEvent::Message(msg) => {
                         let mut xor_addr = XorMappedAddress::default();
@@ -288,38 +406,17 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
                             error!("Failed to get xor mapped message: {}", err);
                             continue;
                         }
-                        let config = CandidateServerReflexiveConfig {
-                            base_config: CandidateConfig {
-                                network: "udp".to_owned(),
-                                address: xor_addr.ip.to_string(),
-                                port: xor_addr.port,
-                                component: 1,
-                                ..Default::default()
-                            },
-                            rel_addr: stun_client.local_addr().ip().to_string(),
-                            rel_port: stun_client.local_addr().port(),
-                            ..Default::default()
-                        };
-                        let candidate = match config.new_candidate_server_reflexive() {
-                            Ok(candidate) => candidate,
-                            Err(err) => {
-                                error!("Failed to new_candidate_server_reflexive: {}", err);
-                                continue;
-                            }
-                        };
-
-                        let candidate_init = match RTCIceCandidate::from(&candidate).to_json() {
+                        let candidate_init = match RTCStunGatherer::new_srflx_candidate(
+                            SocketAddr::new(xor_addr.ip, xor_addr.port),
+                            stun_client.local_addr(),
+                        ) {
                             Ok(candidate_init) => candidate_init,
                             Err(err) => {
-                                error!("Failed to RTCIceCandidate to json: {}", err);
+                                error!("Failed to create srflx candidate: {}", err);
                                 continue;
                             }
                         };
 
-                        four_tuples.insert(FourTuple {
-                            local_addr: stun_client.local_addr(),
-                            peer_addr: stun_client.peer_addr(),
-                        });
                         self.events
                             .push_back(RTCStunGatherEventOut::LocalIceCandidate(candidate_init));
                     }
@@ -333,12 +430,7 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
         for four_tuple in four_tuples {
             if let Some(mut stun_client) = self.stun_clients.remove(&four_tuple) {
                 let _ = stun_client.close();
-
-                if self.stun_clients.is_empty() && self.state != RTCIceGatheringState::Complete {
-                    self.state = RTCIceGatheringState::Complete;
-                    self.events
-                        .push_back(RTCStunGatherEventOut::StunGatheringComplete);
-                }
+                self.maybe_emit_gathering_complete();
             }
         }
 
@@ -346,6 +438,22 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
     }
 
     fn handle_timeout(&mut self, now: Self::Time) -> Result<(), Self::Error> {
+        if self.gather_deadline.is_some_and(|deadline| deadline <= now) {
+            for server in self.pending_servers.drain() {
+                debug!("resolving STUN server {} timed out", server);
+            }
+            for (four_tuple, mut stun_client) in self.stun_clients.drain() {
+                debug!(
+                    "srflx gathering from {} via {} timed out",
+                    four_tuple.local_addr, four_tuple.peer_addr
+                );
+                let _ = stun_client.close();
+            }
+            self.gather_deadline = None;
+            self.maybe_emit_gathering_complete();
+            return Ok(());
+        }
+
         for stun_client in self.stun_clients.values_mut() {
             stun_client.handle_timeout(now)?;
         }
@@ -353,7 +461,7 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
     }
 
     fn poll_timeout(&mut self) -> Option<Self::Time> {
-        let mut eto: Option<Instant> = None;
+        let mut eto: Option<Instant> = self.gather_deadline;
         for stun_client in self.stun_clients.values_mut() {
             if let Some(next) = stun_client.poll_timeout() {
                 eto = Some(eto.map_or(next, |curr| std::cmp::min(curr, next)));
@@ -363,9 +471,274 @@ impl Protocol<TaggedBytesMut, (), RTCStunGatherEventIn> for RTCStunGatherer {
     }
 
     fn close(&mut self) -> Result<(), Self::Error> {
+        self.pending_servers.clear();
         for (_, mut stun_client) in self.stun_clients.drain() {
             let _ = stun_client.close();
         }
+        self.gather_deadline = None;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use rtc::shared::TransportContext;
+    use rtc::stun::error_code::CODE_SERVER_ERROR;
+    use rtc::stun::message::{CLASS_ERROR_RESPONSE, METHOD_BINDING, MessageType};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Arc;
+
+    fn test_local_addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50000)
+    }
+
+    fn test_stun_server_addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3478)
+    }
+
+    fn new_stun_gatherer(gather_timeout: Option<Duration>) -> RTCStunGatherer {
+        RTCStunGatherer::new(
+            vec![test_local_addr()],
+            vec![RTCIceServer {
+                urls: vec!["stun:127.0.0.1:3478".to_owned()],
+                ..Default::default()
+            }],
+            RTCIceTransportPolicy::All,
+            gather_timeout,
+            None,
+            None,
+        )
+    }
+
+    fn resolve_stun_server(gatherer: &mut RTCStunGatherer, server: String) {
+        gatherer
+            .handle_event(RTCStunGatherEventIn::ServerResolved(
+                server,
+                Ok(vec![test_stun_server_addr()]),
+            ))
+            .expect("gatherer should accept the resolved STUN server");
+    }
+
+    fn drain_events(gatherer: &mut RTCStunGatherer) -> Vec<RTCStunGatherEventOut> {
+        let mut events = vec![];
+        while let Some(event) = gatherer.poll_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn emits_host_candidate_before_stun_server_resolves() {
+        let mut gatherer = new_stun_gatherer(None);
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        assert_eq!(servers, vec!["127.0.0.1:3478".to_owned()]);
+        assert!(matches!(
+            drain_events(&mut gatherer).as_slice(),
+            [RTCStunGatherEventOut::LocalIceCandidate(_)]
+        ));
+        assert!(
+            gatherer.poll_write().is_none(),
+            "nothing to send before resolution"
+        );
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Gathering);
+
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        let request = gatherer
+            .poll_write()
+            .expect("Binding request after resolution");
+        assert_eq!(request.transport.local_addr, test_local_addr());
+        assert_eq!(request.transport.peer_addr, test_stun_server_addr());
+        assert!(drain_events(&mut gatherer).is_empty());
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Gathering);
+    }
+
+    #[test]
+    fn completes_gathering_when_stun_transaction_fails() {
+        let mut gatherer = new_stun_gatherer(None);
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        drain_events(&mut gatherer);
+
+        let request = gatherer
+            .poll_write()
+            .expect("Binding request after resolution");
+        let mut request_msg = StunMessage::new();
+        request_msg.raw = request.message.to_vec();
+        request_msg.decode().expect("decode Binding request");
+
+        let mut response = StunMessage::new();
+        response
+            .build(&[
+                Box::new(request_msg.transaction_id),
+                Box::new(MessageType::new(METHOD_BINDING, CLASS_ERROR_RESPONSE)),
+                Box::new(CODE_SERVER_ERROR),
+            ])
+            .expect("failed to build Binding error response");
+        gatherer
+            .handle_read(TaggedBytesMut {
+                now: Instant::now(),
+                transport: TransportContext {
+                    local_addr: test_local_addr(),
+                    peer_addr: test_stun_server_addr(),
+                    ecn: None,
+                    transport_protocol: TransportProtocol::UDP,
+                },
+                message: BytesMut::from(&response.raw[..]),
+            })
+            .expect("gatherer should accept the Binding error response");
+
+        assert!(matches!(
+            drain_events(&mut gatherer).as_slice(),
+            [RTCStunGatherEventOut::StunGatheringComplete]
+        ));
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Complete);
+        assert!(
+            gatherer.poll_timeout().is_none(),
+            "no STUN client should remain after its transaction failed"
+        );
+    }
+
+    #[test]
+    fn completes_gathering_when_stun_server_never_answers() {
+        let timeout = Duration::from_secs(1);
+        let before_gather = Instant::now();
+        let mut gatherer = new_stun_gatherer(Some(timeout));
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        let deadline = gatherer.gather_deadline.expect("gather deadline armed");
+        assert!(deadline >= before_gather + timeout);
+        assert!(deadline <= Instant::now() + timeout);
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        assert!(gatherer.poll_write().is_some(), "initial Binding request");
+        drain_events(&mut gatherer);
+
+        gatherer
+            .handle_timeout(deadline - Duration::from_millis(1))
+            .expect("gatherer should handle an early timeout");
+        assert!(drain_events(&mut gatherer).is_empty());
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Gathering);
+
+        gatherer
+            .handle_timeout(deadline)
+            .expect("gatherer should handle the gather deadline");
+        assert!(matches!(
+            drain_events(&mut gatherer).as_slice(),
+            [RTCStunGatherEventOut::StunGatheringComplete]
+        ));
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Complete);
+        assert!(
+            gatherer.poll_timeout().is_none(),
+            "no STUN client or deadline should remain after the timeout"
+        );
+    }
+
+    #[test]
+    fn completes_gathering_when_stun_server_never_resolves() {
+        let mut gatherer = new_stun_gatherer(Some(Duration::from_secs(1)));
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        drain_events(&mut gatherer);
+        let deadline = gatherer.poll_timeout().expect("gather deadline armed");
+
+        gatherer
+            .handle_timeout(deadline)
+            .expect("gatherer should handle the gather deadline");
+        assert!(matches!(
+            drain_events(&mut gatherer).as_slice(),
+            [RTCStunGatherEventOut::StunGatheringComplete]
+        ));
+
+        // A lookup that finishes after the deadline is ignored.
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        assert!(gatherer.poll_write().is_none());
+        assert!(drain_events(&mut gatherer).is_empty());
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Complete);
+    }
+
+    #[test]
+    fn skips_local_addrs_rejected_by_ip_filter() {
+        let allowed_addr = test_local_addr();
+        let rejected_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 50000);
+        let mut gatherer = RTCStunGatherer::new(
+            vec![allowed_addr, rejected_addr],
+            vec![RTCIceServer {
+                urls: vec!["stun:127.0.0.1:3478".to_owned()],
+                ..Default::default()
+            }],
+            RTCIceTransportPolicy::All,
+            None,
+            Some(Arc::new(move |ip| ip != rejected_addr.ip())),
+            None,
+        );
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        match drain_events(&mut gatherer).as_slice() {
+            [RTCStunGatherEventOut::LocalIceCandidate(candidate)] => {
+                assert!(candidate.candidate.contains(" 127.0.0.1 50000 "));
+            }
+            events => panic!("expected a single host candidate, got {:?}", events),
+        }
+
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        let request = gatherer
+            .poll_write()
+            .expect("Binding request from the allowed address");
+        assert_eq!(request.transport.local_addr, allowed_addr);
+        assert!(
+            gatherer.poll_write().is_none(),
+            "no Binding request from the rejected address"
+        );
+    }
+
+    #[test]
+    fn adds_srflx_candidate_for_nat_1to1_mapped_addr() {
+        let mapped_addr = test_local_addr();
+        let unmapped_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 50000);
+        let external_ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        let mut gatherer = RTCStunGatherer::new(
+            vec![mapped_addr, unmapped_addr],
+            vec![RTCIceServer {
+                urls: vec!["stun:127.0.0.1:3478".to_owned()],
+                ..Default::default()
+            }],
+            RTCIceTransportPolicy::All,
+            None,
+            None,
+            Some(NatOneToOne {
+                mappings: vec![(external_ip, Some(mapped_addr.ip()))],
+                candidate_type: RTCIceCandidateType::Srflx,
+            }),
+        );
+
+        let servers = gatherer.gather().expect("STUN gather should start");
+        let candidates: Vec<String> = drain_events(&mut gatherer)
+            .into_iter()
+            .map(|event| match event {
+                RTCStunGatherEventOut::LocalIceCandidate(candidate) => candidate.candidate,
+                event => panic!("expected only local candidates, got {:?}", event),
+            })
+            .collect();
+        assert_eq!(candidates.len(), 3);
+        assert!(candidates[0].contains(" 127.0.0.1 50000 typ host"));
+        assert!(candidates[1].contains(" 203.0.113.7 50000 typ srflx raddr 127.0.0.1 rport 50000"));
+        assert!(candidates[2].contains(" 127.0.0.2 50000 typ host"));
+        assert_eq!(
+            gatherer.nat_external_addr(mapped_addr),
+            None,
+            "srflx mapping leaves host candidates on the local address"
+        );
+
+        // Only the unmapped address still queries the STUN server.
+        resolve_stun_server(&mut gatherer, servers[0].clone());
+        let request = gatherer
+            .poll_write()
+            .expect("Binding request from the unmapped address");
+        assert_eq!(request.transport.local_addr, unmapped_addr);
+        assert!(gatherer.poll_write().is_none());
+        assert_eq!(gatherer.state(), RTCIceGatheringState::Gathering);
+    }
+}
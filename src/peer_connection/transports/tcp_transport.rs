@@ -1,5 +1,7 @@
 use crate::peer_connection::driver::PeerConnectionDriverEvent;
-use crate::peer_connection::transports::{TcpReadResult, is_retryable_socket_recv_error};
+use crate::peer_connection::transports::{
+    IpFilter, TcpReadResult, is_gather_ip_allowed, is_retryable_socket_recv_error,
+};
 use crate::runtime::{AsyncTcpListener, AsyncTcpStream, Runtime, Sender};
 use bytes::BytesMut;
 use futures::FutureExt;
@@ -229,9 +231,16 @@ impl RTCTcpTransport {
         out
     }
 
-    pub(crate) fn gather_candidates(&self) -> Vec<RTCIceCandidateInit> {
+    pub(crate) fn gather_candidates(
+        &self,
+        ip_filter: Option<&IpFilter>,
+    ) -> Vec<RTCIceCandidateInit> {
         let mut candidates = Vec::new();
         for local_addr in self.listeners.keys() {
+            if !is_gather_ip_allowed(ip_filter, local_addr.ip()) {
+                continue;
+            }
+
             // Gather passive TCP candidate
             let passive_config = CandidateHostConfig {
                 base_config: CandidateConfig {
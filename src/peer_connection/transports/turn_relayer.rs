@@ -1,6 +1,6 @@
 //! TURN relayer for async peer connections.
 
-use crate::runtime;
+use super::{IpFilter, is_gather_ip_allowed};
 use log::{debug, error, trace, warn};
 use rtc::ice::url::SchemeType;
 use rtc::peer_connection::configuration::{RTCIceServer, RTCIceTransportPolicy};
@@ -17,14 +17,17 @@ use rtc::turn::client::{
 };
 use rtc::turn::proto::chandata::ChannelData;
 use std::collections::{HashMap, VecDeque};
+use std::io;
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const MAX_PENDING_PACKETS_PER_PEER: usize = 64;
 
 #[derive(Debug)]
 pub(crate) enum RTCTurnRelayEventIn {
     SocketWriteFailure(FourTuple),
+    /// Result of resolving a TURN server address returned by `gather()`.
+    ServerResolved(String, io::Result<Vec<SocketAddr>>),
 }
 
 #[derive(Debug)]
@@ -39,6 +42,13 @@ struct PendingPermission {
     peer_addr: SocketAddr,
 }
 
+#[derive(Debug)]
+struct PendingTurnUrl {
+    url: String,
+    username: String,
+    password: String,
+}
+
 struct ManagedTurnClient {
     client: TurnClient,
     url: String,
@@ -53,6 +63,10 @@ pub(crate) struct RTCTurnRelayer {
     ice_servers: Vec<RTCIceServer>,
     ice_gather_policy: RTCIceTransportPolicy,
     state: RTCIceGatheringState,
+    gather_timeout: Option<Duration>,
+    gather_deadline: Option<Instant>,
+    ip_filter: Option<IpFilter>,
+    pending_servers: HashMap<String, Vec<PendingTurnUrl>>,
     clients: HashMap<FourTuple, ManagedTurnClient>,
     relay_addrs: HashMap<SocketAddr, FourTuple>,
     pending_permissions: HashMap<rtc::stun::message::TransactionId, PendingPermission>,
@@ -64,16 +78,26 @@ pub(crate) struct RTCTurnRelayer {
 }
 
 impl RTCTurnRelayer {
+    /// `gather_timeout` bounds how long relay gathering waits for TURN
+    /// servers to resolve and allocate; `None` relies on the TURN client's own
+    /// transaction timeouts. Local addresses rejected by `ip_filter` are not
+    /// used for TURN allocations.
     pub(crate) fn new(
         local_addrs: Vec<SocketAddr>,
         ice_servers: Vec<RTCIceServer>,
         ice_gather_policy: RTCIceTransportPolicy,
+        gather_timeout: Option<Duration>,
+        ip_filter: Option<IpFilter>,
     ) -> Self {
         Self {
             local_addrs,
             ice_servers,
             ice_gather_policy,
             state: RTCIceGatheringState::New,
+            gather_timeout,
+            gather_deadline: None,
+            ip_filter,
+            pending_servers: HashMap::new(),
             clients: HashMap::new(),
             relay_addrs: HashMap::new(),
             pending_permissions: HashMap::new(),
@@ -97,107 +121,130 @@ impl RTCTurnRelayer {
         self.relay_addrs.contains_key(&local_addr)
     }
 
-    pub(crate) async fn gather(&mut self) -> Result<()> {
+    /// Start gathering: the TURN servers to resolve are returned so the
+    /// caller can look them up off the event loop and report back with
+    /// [`RTCTurnRelayEventIn::ServerResolved`].
+    pub(crate) fn gather(&mut self) -> Result<Vec<String>> {
         if self.state == RTCIceGatheringState::Gathering {
-            return Ok(());
+            return Ok(vec![]);
         }
 
         if self.state == RTCIceGatheringState::Complete {
             self.emit_existing_candidates()?;
             self.events
                 .push_back(RTCTurnRelayEventOut::TurnGatheringComplete);
-            return Ok(());
+            return Ok(vec![]);
         }
 
         self.state = RTCIceGatheringState::Gathering;
+        // Armed before resolving TURN servers so DNS lookups count against the timeout.
+        self.gather_deadline = self.gather_timeout.map(|timeout| Instant::now() + timeout);
 
-        for ice_server in &self.ice_servers {
-            let urls = ice_server.urls()?;
-
-            for url in urls {
-                if !matches!(url.scheme, SchemeType::Turn | SchemeType::Turns) {
-                    continue;
-                }
-
-                if url.is_secure() {
-                    warn!("Skipping unsupported secure TURN url {}", url);
-                    continue;
-                }
+        if !self.gather_addrs().is_empty() {
+            for ice_server in &self.ice_servers {
+                let urls = ice_server.urls()?;
 
-                if url.proto.to_string() != "udp" {
-                    warn!("Skipping unsupported non-UDP TURN url {}", url);
-                    continue;
-                }
-
-                let turn_server_addr = format!("{}:{}", url.host, url.port);
-                let resolved_addrs = match runtime::resolve_host(&turn_server_addr).await {
-                    Ok(addrs) => addrs,
-                    Err(err) => {
-                        error!(
-                            "Failed to resolve TURN server {}: {}",
-                            turn_server_addr, err
-                        );
+                for url in urls {
+                    if !matches!(url.scheme, SchemeType::Turn | SchemeType::Turns) {
                         continue;
                     }
-                };
 
-                for local_addr in &self.local_addrs {
-                    let Some(peer_addr) = resolved_addrs
-                        .iter()
-                        .copied()
-                        .find(|addr| addr.is_ipv4() == local_addr.is_ipv4())
-                    else {
+                    if url.is_secure() {
+                        warn!("Skipping unsupported secure TURN url {}", url);
                         continue;
-                    };
+                    }
 
-                    let four_tuple = FourTuple {
-                        local_addr: *local_addr,
-                        peer_addr,
-                    };
-                    if self.clients.contains_key(&four_tuple) {
+                    if url.proto.to_string() != "udp" {
+                        warn!("Skipping unsupported non-UDP TURN url {}", url);
                         continue;
                     }
 
-                    let mut client = TurnClient::new(TurnClientConfig {
-                        stun_serv_addr: peer_addr.to_string(),
-                        turn_serv_addr: peer_addr.to_string(),
-                        local_addr: *local_addr,
-                        transport_protocol: TransportProtocol::UDP,
-                        username: url.username.clone(),
-                        password: url.password.clone(),
-                        realm: String::new(),
-                        software: String::new(),
-                        rto_in_ms: 0,
-                    })?;
-
-                    let allocate_tid = client.allocate()?;
-                    debug!(
-                        "TURN allocation started from {} to {} via {}",
-                        local_addr, peer_addr, url
-                    );
-
-                    self.clients.insert(
-                        four_tuple,
-                        ManagedTurnClient {
-                            client,
+                    self.pending_servers
+                        .entry(format!("{}:{}", url.host, url.port))
+                        .or_default()
+                        .push(PendingTurnUrl {
                             url: url.to_string(),
-                            allocate_tid,
-                            local_addr: *local_addr,
-                            relay_addr: None,
-                            gather_finished: false,
-                        },
-                    );
+                            username: url.username.clone(),
+                            password: url.password.clone(),
+                        });
                 }
             }
         }
 
-        if self.clients.is_empty() {
-            self.state = RTCIceGatheringState::Complete;
-            self.events
-                .push_back(RTCTurnRelayEventOut::TurnGatheringComplete);
-        }
+        self.maybe_emit_gathering_complete();
+        Ok(self.pending_servers.keys().cloned().collect())
+    }
 
-        Ok(())
+    /// Local socket addresses allowed by the IP filter
+    fn gather_addrs(&self) -> Vec<SocketAddr> {
+        self.local_addrs
+            .iter()
+            .copied()
+            .filter(|local_addr| is_gather_ip_allowed(self.ip_filter.as_ref(), local_addr.ip()))
+            .collect()
+    }
+
+    /// Start TURN allocations from every local address towards a resolved TURN server
+    fn start_allocations(&mut self, turn_url: &PendingTurnUrl, resolved_addrs: &[SocketAddr]) {
+        for local_addr in self.gather_addrs() {
+            let Some(peer_addr) = resolved_addrs
+                .iter()
+                .copied()
+                .find(|addr| addr.is_ipv4() == local_addr.is_ipv4())
+            else {
+                continue;
+            };
+
+            let four_tuple = FourTuple {
+                local_addr: local_addr,
+                peer_addr,
+            };
+            if self.clients.contains_key(&four_tuple) {
+                continue;
+            }
+
+            let allocation = TurnClient::new(TurnClientConfig {
+                stun_serv_addr: peer_addr.to_string(),
+                turn_serv_addr: peer_addr.to_string(),
+                local_addr: local_addr,
+                transport_protocol: TransportProtocol::UDP,
+                username: turn_url.username.clone(),
+                password: turn_url.password.clone(),
+                realm: String::new(),
+                software: String::new(),
+                rto_in_ms: 0,
+            })
+            .and_then(|mut client| {
+                let allocate_tid = client.allocate()?;
+                Ok((client, allocate_tid))
+            });
+            let (client, allocate_tid) = match allocation {
+                Ok(allocation) => allocation,
+                Err(err) => {
+                    error!(
+                        "Failed to start TURN allocation from {} to {} via {}: {}",
+                        local_addr, peer_addr, turn_url.url, err
+                    );
+                    continue;
+                }
+            };
+            debug!(
+                "TURN allocation started from {} to {} via {}",
+                local_addr, peer_addr, turn_url.url
+            );
+
+            self.clients.insert(
+                four_tuple,
+                ManagedTurnClient {
+                    client,
+                    url: turn_url.url.clone(),
+                    allocate_tid,
+                    local_addr: local_addr,
+                    relay_addr: None,
+                    gather_finished: false,
+                },
+            );
+        }
     }
 
     fn emit_existing_candidates(&mut self) -> Result<()> {
@@ -243,9 +290,11 @@ impl RTCTurnRelayer {
 
     fn maybe_emit_gathering_complete(&mut self) {
         if self.state == RTCIceGatheringState::Gathering
+            && self.pending_servers.is_empty()
             && self.clients.values().all(|client| client.gather_finished)
         {
             self.state = RTCIceGatheringState::Complete;
+            self.gather_deadline = None;
             self.events
                 .push_back(RTCTurnRelayEventOut::TurnGatheringComplete);
         }
@@ -468,6 +517,21 @@ impl Protocol<TaggedBytesMut, TaggedBytesMut, RTCTurnRelayEventIn> for RTCTurnRe
                 self.remove_client(four_tuple);
                 self.maybe_emit_gathering_complete();
             }
+            RTCTurnRelayEventIn::ServerResolved(server, resolved_addrs) => {
+                // Ignore lookups that finished after the gather deadline or close().
+                let Some(turn_urls) = self.pending_servers.remove(&server) else {
+                    return Ok(());
+                };
+                match resolved_addrs {
+                    Ok(resolved_addrs) => {
+                        for turn_url in &turn_urls {
+                            self.start_allocations(turn_url, &resolved_addrs);
+                        }
+                    }
+                    Err(err) => error!("Failed to resolve TURN server {}: {}", server, err),
+                }
+                self.maybe_emit_gathering_complete();
+            }
         }
         Ok(())
     }
@@ -582,6 +646,28 @@ impl Protocol<TaggedBytesMut, TaggedBytesMut, RTCTurnRelayEventIn> for RTCTurnRe
     }
 
     fn handle_timeout(&mut self, now: Self::Time) -> Result<()> {
+        if self.gather_deadline.is_some_and(|deadline| deadline <= now) {
+            self.gather_deadline = None;
+            for server in self.pending_servers.keys() {
+                warn!("Resolving TURN server {} timed out", server);
+            }
+            self.pending_servers.clear();
+            let unfinished: Vec<FourTuple> = self
+                .clients
+                .iter()
+                .filter(|(_, managed_client)| !managed_client.gather_finished)
+                .map(|(four_tuple, _)| *four_tuple)
+                .collect();
+            for four_tuple in unfinished {
+                warn!(
+                    "TURN allocation from {} to {} timed out",
+                    four_tuple.local_addr, four_tuple.peer_addr
+                );
+                self.remove_client(four_tuple);
+            }
+            self.maybe_emit_gathering_complete();
+        }
+
         for managed_client in self.clients.values_mut() {
             managed_client.client.handle_timeout(now)?;
         }
@@ -589,7 +675,7 @@ impl Protocol<TaggedBytesMut, TaggedBytesMut, RTCTurnRelayEventIn> for RTCTurnRe
     }
 
     fn poll_timeout(&mut self) -> Option<Self::Time> {
-        let mut eto = None;
+        let mut eto = self.gather_deadline;
         for managed_client in self.clients.values_mut() {
             if let Some(next) = managed_client.client.poll_timeout() {
                 eto = Some(eto.map_or(next, |current| std::cmp::min(current, next)));
@@ -603,6 +689,8 @@ impl Protocol<TaggedBytesMut, TaggedBytesMut, RTCTurnRelayEventIn> for RTCTurnRe
         for key in keys {
             self.remove_client(key);
         }
+        self.pending_servers.clear();
+        self.gather_deadline = None;
         Ok(())
     }
 }
@@ -614,18 +702,21 @@ mod tests {
     use rtc::peer_connection::configuration::RTCIceServer;
     use rtc::stun::attributes::{ATTR_NONCE, ATTR_REALM};
     use rtc::stun::error_code::CODE_UNAUTHORIZED;
-    use rtc::stun::message::{CLASS_ERROR_RESPONSE, MessageType, TransactionId};
+    use rtc::stun::integrity::MessageIntegrity;
+    use rtc::stun::message::{
+        CLASS_ERROR_RESPONSE, CLASS_SUCCESS_RESPONSE, METHOD_ALLOCATE, MessageType, TransactionId,
+    };
     use rtc::stun::textattrs::{Nonce, Realm};
+    use rtc::stun::xoraddr::XorMappedAddress;
+    use rtc::turn::proto::lifetime::Lifetime;
+    use rtc::turn::proto::relayaddr::RelayedAddress;
     use std::net::{IpAddr, Ipv4Addr};
 
     fn build_turn_allocate_unauthorized(transaction_id: TransactionId) -> StunMessage {
         let mut msg = StunMessage::new();
         msg.build(&[
             Box::new(transaction_id),
-            Box::new(MessageType::new(
-                rtc::stun::message::METHOD_ALLOCATE,
-                CLASS_ERROR_RESPONSE,
-            )),
+            Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
             Box::new(CODE_UNAUTHORIZED),
             Box::new(Realm::new(ATTR_REALM, "webrtc.rs".to_owned())),
             Box::new(Nonce::new(ATTR_NONCE, "nonce".to_owned())),
@@ -634,60 +725,273 @@ mod tests {
         msg
     }
 
+    fn build_turn_allocate_success(
+        transaction_id: TransactionId,
+        relay_addr: SocketAddr,
+        mapped_addr: SocketAddr,
+    ) -> StunMessage {
+        let mut msg = StunMessage::new();
+        msg.build(&[
+            Box::new(transaction_id),
+            Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_SUCCESS_RESPONSE)),
+            Box::new(RelayedAddress {
+                ip: relay_addr.ip(),
+                port: relay_addr.port(),
+            }),
+            Box::new(XorMappedAddress {
+                ip: mapped_addr.ip(),
+                port: mapped_addr.port(),
+            }),
+            Box::new(Lifetime(Duration::from_secs(600))),
+            Box::new(MessageIntegrity::new_long_term_integrity(
+                "user".to_owned(),
+                "webrtc.rs".to_owned(),
+                "pass".to_owned(),
+            )),
+        ])
+        .expect("failed to build TURN allocate success response");
+        msg
+    }
+
+    fn test_local_addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50000)
+    }
+
+    fn test_turn_peer_addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3478)
+    }
+
+    fn new_turn_relayer(
+        local_addrs: Vec<SocketAddr>,
+        gather_timeout: Option<Duration>,
+    ) -> RTCTurnRelayer {
+        RTCTurnRelayer::new(
+            local_addrs,
+            vec![RTCIceServer {
+                urls: vec![format!("turn:{}?transport=udp", test_turn_peer_addr())],
+                username: "user".to_owned(),
+                credential: "pass".to_owned(),
+            }],
+            RTCIceTransportPolicy::Relay,
+            gather_timeout,
+            None,
+        )
+    }
+
+    fn start_gathering(relayer: &mut RTCTurnRelayer) {
+        let servers = relayer.gather().expect("TURN gather should start");
+        assert_eq!(servers, vec![test_turn_peer_addr().to_string()]);
+        assert!(
+            relayer.poll_write().is_none(),
+            "nothing to send before resolution"
+        );
+
+        for server in servers {
+            relayer
+                .handle_event(RTCTurnRelayEventIn::ServerResolved(
+                    server,
+                    Ok(vec![test_turn_peer_addr()]),
+                ))
+                .expect("relayer should accept the resolved TURN server");
+        }
+    }
+
+    fn write_from(relayer: &mut RTCTurnRelayer, local_addr: SocketAddr) -> TaggedBytesMut {
+        let mut writes = vec![];
+        while let Some(msg) = relayer.poll_write() {
+            writes.push(msg);
+        }
+        writes
+            .into_iter()
+            .find(|msg| msg.transport.local_addr == local_addr)
+            .expect("TURN request from local address")
+    }
+
+    fn transaction_id(msg: &TaggedBytesMut) -> TransactionId {
+        let mut stun_message = StunMessage::new();
+        stun_message.raw = msg.message.to_vec();
+        stun_message.decode().expect("decode TURN request");
+        stun_message.transaction_id
+    }
+
+    fn turn_response(local_addr: SocketAddr, response: &StunMessage) -> TaggedBytesMut {
+        TaggedBytesMut {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr,
+                peer_addr: test_turn_peer_addr(),
+                ecn: None,
+                transport_protocol: TransportProtocol::UDP,
+            },
+            message: BytesMut::from(&response.raw[..]),
+        }
+    }
+
     #[test]
     fn routes_turn_allocate_response_by_local_addr_and_port() {
-        crate::runtime::block_on(async {
-            let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50000);
-            let turn_peer_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3478);
-            let mut relayer = RTCTurnRelayer::new(
-                vec![local_addr],
-                vec![RTCIceServer {
-                    urls: vec![format!("turn:{}?transport=udp", turn_peer_addr)],
-                    username: "user".to_owned(),
-                    credential: "pass".to_owned(),
-                }],
-                RTCIceTransportPolicy::Relay,
-            );
+        let local_addr = test_local_addr();
+        let turn_peer_addr = test_turn_peer_addr();
+        let mut relayer = new_turn_relayer(vec![local_addr], None);
+
+        start_gathering(&mut relayer);
+        let initial_request = relayer.poll_write().expect("initial Allocate request");
+        assert_eq!(initial_request.transport.peer_addr, turn_peer_addr);
+
+        let response = build_turn_allocate_unauthorized(transaction_id(&initial_request));
+        let msg = TaggedBytesMut {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr,
+                peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 3478),
+                ecn: None,
+                transport_protocol: TransportProtocol::UDP,
+            },
+            message: BytesMut::from(&response.raw[..]),
+        };
 
-            relayer.gather().await.expect("TURN gather should start");
-            let initial_request = relayer.poll_write().expect("initial Allocate request");
-            assert_eq!(initial_request.transport.peer_addr, turn_peer_addr);
-
-            let mut initial_request_msg = StunMessage::new();
-            initial_request_msg.raw = initial_request.message.to_vec();
-            initial_request_msg
-                .decode()
-                .expect("decode initial Allocate request");
-
-            let response = build_turn_allocate_unauthorized(initial_request_msg.transaction_id);
-            let msg = TaggedBytesMut {
-                now: Instant::now(),
-                transport: TransportContext {
-                    local_addr,
-                    peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 3478),
-                    ecn: None,
-                    transport_protocol: TransportProtocol::UDP,
-                },
-                message: BytesMut::from(&response.raw[..]),
-            };
+        assert!(
+            relayer.is_turn_message(&msg),
+            "TURN error response on the same local socket and TURN port should route to the relayer"
+        );
+
+        relayer
+            .handle_read(msg)
+            .expect("relayer should accept TURN unauthorized response");
+
+        let retry_request = relayer
+            .poll_write()
+            .expect("authenticated Allocate retry after unauthorized response");
+        assert_eq!(retry_request.transport.peer_addr.port(), 3478);
+        assert!(
+            retry_request.message.len() > initial_request.message.len(),
+            "authenticated retry should be larger than the unauthenticated Allocate request"
+        );
+    }
 
-            assert!(
-                relayer.is_turn_message(&msg),
-                "TURN error response on the same local socket and TURN port should route to the relayer"
-            );
+    #[test]
+    fn completes_gathering_when_turn_allocation_times_out() {
+        let timeout = Duration::from_secs(1);
+        let before_gather = Instant::now();
+        let mut relayer = new_turn_relayer(vec![test_local_addr()], Some(timeout));
+
+        start_gathering(&mut relayer);
+        let deadline = relayer.gather_deadline.expect("gather deadline armed");
+        assert!(deadline >= before_gather + timeout);
+        assert!(deadline <= Instant::now() + timeout);
+        assert!(relayer.poll_write().is_some(), "initial Allocate request");
+        assert!(relayer.poll_event().is_none());
+
+        relayer
+            .handle_timeout(deadline - Duration::from_millis(1))
+            .expect("relayer should handle an early timeout");
+        assert!(relayer.poll_event().is_none());
+        assert_eq!(relayer.state(), RTCIceGatheringState::Gathering);
+
+        relayer
+            .handle_timeout(deadline)
+            .expect("relayer should handle the gather deadline");
+
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::TurnGatheringComplete)
+        ));
+        assert_eq!(relayer.state(), RTCIceGatheringState::Complete);
+        assert!(
+            relayer.poll_timeout().is_none(),
+            "no TURN client or deadline should remain after the timeout"
+        );
+    }
 
-            relayer
-                .handle_read(msg)
-                .expect("relayer should accept TURN unauthorized response");
-
-            let retry_request = relayer
-                .poll_write()
-                .expect("authenticated Allocate retry after unauthorized response");
-            assert_eq!(retry_request.transport.peer_addr.port(), 3478);
-            assert!(
-                retry_request.message.len() > initial_request.message.len(),
-                "authenticated retry should be larger than the unauthenticated Allocate request"
-            );
-        });
+    #[test]
+    fn completes_gathering_when_turn_server_never_resolves() {
+        let mut relayer = new_turn_relayer(vec![test_local_addr()], Some(Duration::from_secs(1)));
+
+        let servers = relayer.gather().expect("TURN gather should start");
+        let deadline = relayer.poll_timeout().expect("gather deadline armed");
+        relayer
+            .handle_timeout(deadline)
+            .expect("relayer should handle the gather deadline");
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::TurnGatheringComplete)
+        ));
+
+        // A lookup that finishes after the deadline is ignored.
+        relayer
+            .handle_event(RTCTurnRelayEventIn::ServerResolved(
+                servers[0].clone(),
+                Ok(vec![test_turn_peer_addr()]),
+            ))
+            .expect("relayer should ignore a late TURN server resolution");
+        assert!(relayer.poll_write().is_none());
+        assert!(relayer.poll_event().is_none());
+        assert_eq!(relayer.state(), RTCIceGatheringState::Complete);
+    }
+
+    #[test]
+    fn keeps_finished_relay_when_other_allocation_times_out() {
+        let finished_local_addr = test_local_addr();
+        let pending_local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50001);
+        let relay_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)), 40000);
+        let mut relayer = new_turn_relayer(
+            vec![finished_local_addr, pending_local_addr],
+            Some(Duration::from_secs(1)),
+        );
+
+        start_gathering(&mut relayer);
+        let deadline = relayer.gather_deadline.expect("gather deadline armed");
+
+        let initial_request = write_from(&mut relayer, finished_local_addr);
+        relayer
+            .handle_read(turn_response(
+                finished_local_addr,
+                &build_turn_allocate_unauthorized(transaction_id(&initial_request)),
+            ))
+            .expect("relayer should accept TURN unauthorized response");
+        let retry_request = write_from(&mut relayer, finished_local_addr);
+        relayer
+            .handle_read(turn_response(
+                finished_local_addr,
+                &build_turn_allocate_success(
+                    transaction_id(&retry_request),
+                    relay_addr,
+                    finished_local_addr,
+                ),
+            ))
+            .expect("relayer should accept TURN allocate success response");
+
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::LocalIceCandidate(_))
+        ));
+        assert!(
+            relayer.poll_event().is_none(),
+            "gathering waits for the pending allocation"
+        );
+
+        relayer
+            .handle_timeout(deadline)
+            .expect("relayer should handle the gather deadline");
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::TurnGatheringComplete)
+        ));
+        assert_eq!(relayer.state(), RTCIceGatheringState::Complete);
+
+        let probe = build_turn_allocate_unauthorized(TransactionId::default());
+        assert!(relayer.contains_local_addr(relay_addr));
+        assert!(relayer.is_turn_message(&turn_response(finished_local_addr, &probe)));
+        assert!(!relayer.is_turn_message(&turn_response(pending_local_addr, &probe)));
+
+        // Gathering again re-announces the relay candidate that survived the deadline.
+        assert!(relayer.gather().expect("TURN gather again").is_empty());
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::LocalIceCandidate(_))
+        ));
+        assert!(matches!(
+            relayer.poll_event(),
+            Some(RTCTurnRelayEventOut::TurnGatheringComplete)
+        ));
     }
 }